@@ -7,24 +7,83 @@ extern crate doc_comment;
 #[cfg(doctest)]
 doctest!("../readme.md");
 
+#[cfg(feature = "std")]
+extern crate std;
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+
 use core::cmp::{Eq, Ord, Ordering};
 use core::fmt::Debug;
 use core::mem::{forget, ManuallyDrop};
 use core::ops::{Deref, DerefMut};
 
+/// Controls *when* a [`WithDrop`]'s custom destructor runs.
+///
+/// The `OnUnwind` and `OnSuccess` strategies rely on [`std::thread::panicking`]
+/// to tell a rollback apart from a commit, so they are only available when
+/// the crate is built with the `std` feature.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Strategy {
+    /// Always run the closure, whether or not a panic is in progress.
+    ///
+    /// This is the behavior `WithDrop` has always had.
+    Always,
+    /// Only run the closure while unwinding from a panic.
+    ///
+    /// Useful for rollback-style cleanup: undo a half-finished operation
+    /// if the scope panicked, but leave things alone on success.
+    #[cfg(feature = "std")]
+    OnUnwind,
+    /// Only run the closure on a normal, non-panicking drop.
+    ///
+    /// Useful for commit-style cleanup that must not run if the scope panicked.
+    #[cfg(feature = "std")]
+    OnSuccess,
+}
+
 /// Container that holds a value and a customestructor
 #[derive(Clone, Debug, Eq, Ord)]
 pub struct WithDrop<T, F: FnOnce(T)> {
     data: ManuallyDrop<(T, F)>,
+    strategy: Strategy,
 }
 
 impl<T, F: FnOnce(T)> WithDrop<T, F> {
     pub fn new(inner: T, drop_fn: F) -> Self {
+        Self::with_strategy(inner, drop_fn, Strategy::Always)
+    }
+
+    /// Like [`Self::new`], but the closure only runs when `strategy` says it should.
+    pub fn with_strategy(inner: T, drop_fn: F, strategy: Strategy) -> Self {
         Self {
             data: ManuallyDrop::new((inner, drop_fn)),
+            strategy,
         }
     }
 
+    /// Shorthand for [`Self::with_strategy`] with [`Strategy::Always`].
+    ///
+    /// Equivalent to [`Self::new`].
+    pub fn always(inner: T, drop_fn: F) -> Self {
+        Self::with_strategy(inner, drop_fn, Strategy::Always)
+    }
+
+    /// Shorthand for [`Self::with_strategy`] with [`Strategy::OnUnwind`].
+    #[cfg(feature = "std")]
+    pub fn on_unwind(inner: T, drop_fn: F) -> Self {
+        Self::with_strategy(inner, drop_fn, Strategy::OnUnwind)
+    }
+
+    /// Shorthand for [`Self::with_strategy`] with [`Strategy::OnSuccess`].
+    #[cfg(feature = "std")]
+    pub fn on_success(inner: T, drop_fn: F) -> Self {
+        Self::with_strategy(inner, drop_fn, Strategy::OnSuccess)
+    }
+
     /// This extracts the contained value while dropping the closure
     /// and the container.
     ///
@@ -34,6 +93,11 @@ impl<T, F: FnOnce(T)> WithDrop<T, F> {
         forget(self);
         v
     }
+
+    /// Alias for [`Self::into_inner`].
+    pub fn disarm(self) -> T {
+        self.into_inner()
+    }
 }
 
 impl<T: PartialEq<T>, F1: FnOnce(T), F2: FnOnce(T)> PartialEq<WithDrop<T, F2>> for WithDrop<T, F1> {
@@ -53,7 +117,21 @@ impl<T: PartialOrd<T>, F1: FnOnce(T), F2: FnOnce(T)> PartialOrd<WithDrop<T, F2>>
 impl<T, F: FnOnce(T)> Drop for WithDrop<T, F> {
     fn drop(&mut self) {
         let (v, f) = unsafe { ManuallyDrop::take(&mut self.data) };
-        f(v);
+        match self.strategy {
+            Strategy::Always => f(v),
+            #[cfg(feature = "std")]
+            Strategy::OnUnwind => {
+                if std::thread::panicking() {
+                    f(v);
+                }
+            }
+            #[cfg(feature = "std")]
+            Strategy::OnSuccess => {
+                if !std::thread::panicking() {
+                    f(v);
+                }
+            }
+        }
     }
 }
 
@@ -74,3 +152,161 @@ impl<T, F: FnOnce(T)> DerefMut for WithDrop<T, F> {
 pub fn with_drop<T, F: FnOnce(T)>(inner: T, drop_fn: F) -> WithDrop<T, F> {
     WithDrop::new(inner, drop_fn)
 }
+
+/// Container that holds a value and a destructor that mutates the value
+/// in place, rather than consuming it.
+///
+/// Unlike [`WithDrop`], the closure only gets `&mut T`, so `T`'s own
+/// destructor still runs afterwards. This suits cleanups that adjust the
+/// value rather than replace it, e.g. flushing a buffer or zeroing a secret.
+#[derive(Clone, Debug, Eq, Ord)]
+pub struct WithDropMut<T, F: FnOnce(&mut T)> {
+    data: ManuallyDrop<(T, F)>,
+}
+
+impl<T, F: FnOnce(&mut T)> WithDropMut<T, F> {
+    pub fn new(inner: T, drop_fn: F) -> Self {
+        Self {
+            data: ManuallyDrop::new((inner, drop_fn)),
+        }
+    }
+
+    /// This extracts the contained value while dropping the closure
+    /// and the container.
+    ///
+    /// The custom closure will *not* be executed; `T`'s own destructor
+    /// still runs once the returned value goes out of scope.
+    pub fn into_inner(mut self) -> T {
+        let (v, _) = unsafe { ManuallyDrop::take(&mut self.data) };
+        forget(self);
+        v
+    }
+
+    /// Alias for [`Self::into_inner`].
+    pub fn disarm(self) -> T {
+        self.into_inner()
+    }
+}
+
+impl<T: PartialEq<T>, F1: FnOnce(&mut T), F2: FnOnce(&mut T)> PartialEq<WithDropMut<T, F2>>
+    for WithDropMut<T, F1>
+{
+    fn eq(&self, other: &WithDropMut<T, F2>) -> bool {
+        self.deref().eq(other.deref())
+    }
+}
+
+impl<T: PartialOrd<T>, F1: FnOnce(&mut T), F2: FnOnce(&mut T)> PartialOrd<WithDropMut<T, F2>>
+    for WithDropMut<T, F1>
+{
+    fn partial_cmp(&self, other: &WithDropMut<T, F2>) -> Option<Ordering> {
+        self.deref().partial_cmp(other.deref())
+    }
+}
+
+impl<T, F: FnOnce(&mut T)> Drop for WithDropMut<T, F> {
+    fn drop(&mut self) {
+        let (mut v, f) = unsafe { ManuallyDrop::take(&mut self.data) };
+        f(&mut v);
+    }
+}
+
+impl<T, F: FnOnce(&mut T)> Deref for WithDropMut<T, F> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &(*self.data).0
+    }
+}
+
+impl<T, F: FnOnce(&mut T)> DerefMut for WithDropMut<T, F> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut (*self.data).0
+    }
+}
+
+/// Alias for WithDropMut::new
+pub fn with_drop_mut<T, F: FnOnce(&mut T)>(inner: T, drop_fn: F) -> WithDropMut<T, F> {
+    WithDropMut::new(inner, drop_fn)
+}
+
+/// A guard that holds no data of its own and just runs a closure on drop,
+/// mirroring Go/Zig's `defer`.
+///
+/// Use this when the state a cleanup needs lives in the closure itself and
+/// there is no meaningful value to `Deref` to.
+#[derive(Clone, Debug)]
+pub struct Defer<F: FnOnce()> {
+    f: ManuallyDrop<F>,
+}
+
+impl<F: FnOnce()> Defer<F> {
+    pub fn new(f: F) -> Self {
+        Self {
+            f: ManuallyDrop::new(f),
+        }
+    }
+
+    /// Cancels the pending action; `f` will *not* run.
+    pub fn disarm(mut self) {
+        unsafe { ManuallyDrop::drop(&mut self.f) };
+        forget(self);
+    }
+}
+
+impl<F: FnOnce()> Drop for Defer<F> {
+    fn drop(&mut self) {
+        let f = unsafe { ManuallyDrop::take(&mut self.f) };
+        f();
+    }
+}
+
+/// Alias for Defer::new
+pub fn defer<F: FnOnce()>(f: F) -> Defer<F> {
+    Defer::new(f)
+}
+
+/// A [`WithDrop`] whose destructor is a plain function pointer rather than
+/// an unnameable closure type.
+///
+/// `fn(T)` is nameable, so unlike `WithDrop<T, F>` in general,
+/// `WithDropFn<T>` can appear in struct fields and function return types.
+pub type WithDropFn<T> = WithDrop<T, fn(T)>;
+
+/// Constructs a [`WithDropFn`] from a plain function pointer.
+pub fn with_drop_fn<T>(inner: T, drop_fn: fn(T)) -> WithDropFn<T> {
+    WithDrop::new(inner, drop_fn)
+}
+
+/// Like [`WithDropFn`], but stores its destructor as a boxed trait object
+/// instead of a function pointer.
+///
+/// This makes `WithDropDyn<T>` nameable without fixing the closure to `fn(T)`,
+/// so heterogeneous guards can be collected into e.g. a `Vec<WithDropDyn<T>>`.
+/// `into_inner`, `Drop` and `Deref`/`DerefMut` are inherited unchanged from
+/// [`WithDrop`], since `Box<dyn FnOnce(T)>` itself implements `FnOnce(T)`.
+#[cfg(feature = "alloc")]
+pub type WithDropDyn<T> = WithDrop<T, Box<dyn FnOnce(T)>>;
+
+/// Constructs a [`WithDropDyn`] from any `'static` closure, boxing it.
+#[cfg(feature = "alloc")]
+pub fn with_drop_boxed<T>(inner: T, drop_fn: impl FnOnce(T) + 'static) -> WithDropDyn<T> {
+    WithDrop::new(inner, Box::new(drop_fn))
+}
+
+/// Extension trait that adds a fluent `value.with_drop(closure)` constructor
+/// to every type.
+///
+/// Brought into scope via `use with_drop::prelude::*;`.
+pub trait WithDropExt: Sized {
+    /// Wraps `self` in a [`WithDrop`] that runs `f` on drop.
+    fn with_drop<F: FnOnce(Self)>(self, f: F) -> WithDrop<Self, F> {
+        WithDrop::new(self, f)
+    }
+}
+
+impl<T> WithDropExt for T {}
+
+/// Convenience re-exports for `use with_drop::prelude::*;`.
+pub mod prelude {
+    pub use crate::WithDropExt;
+}
@@ -1,5 +1,14 @@
-use with_drop::{with_drop, WithDrop};
+use with_drop::{
+    defer, prelude::*, with_drop, with_drop_fn, with_drop_mut, Defer, WithDrop, WithDropFn,
+    WithDropMut,
+};
 use std::{cell::RefCell, vec::Vec};
+#[cfg(feature = "std")]
+use std::panic;
+#[cfg(feature = "alloc")]
+use with_drop::{with_drop_boxed, WithDropDyn};
+#[cfg(feature = "alloc")]
+use std::rc::Rc;
 
 #[test]
 fn test_drop_with() {
@@ -29,3 +38,175 @@ fn test_drop_with() {
 
     assert!(*drops.borrow() == [23, 32, 65]);
 }
+
+#[cfg(feature = "std")]
+#[test]
+fn test_on_success_runs_without_panic() {
+    let drops = RefCell::new(Vec::new());
+
+    {
+        let _a = WithDrop::on_success(23, |x| drops.borrow_mut().push(x));
+    };
+
+    assert!(*drops.borrow() == [23]);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_on_success_skips_during_unwind() {
+    let drops = RefCell::new(Vec::new());
+
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        let _a = WithDrop::on_success(23, |x| drops.borrow_mut().push(x));
+        panic!("boom");
+    }));
+
+    assert!(result.is_err());
+    assert!(drops.borrow().is_empty());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_on_unwind_skips_without_panic() {
+    let drops = RefCell::new(Vec::new());
+
+    {
+        let _a = WithDrop::on_unwind(23, |x| drops.borrow_mut().push(x));
+    };
+
+    assert!(drops.borrow().is_empty());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_on_unwind_runs_during_unwind() {
+    let drops = RefCell::new(Vec::new());
+
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        let _a = WithDrop::on_unwind(23, |x| drops.borrow_mut().push(x));
+        panic!("boom");
+    }));
+
+    assert!(result.is_err());
+    assert!(*drops.borrow() == [23]);
+}
+
+#[test]
+fn test_disarm() {
+    let drops = RefCell::new(Vec::new());
+
+    let a = WithDrop::new(23, |x| drops.borrow_mut().push(x));
+    assert!(a.disarm() == 23);
+    assert!(drops.borrow().is_empty());
+}
+
+#[test]
+fn test_drop_with_mut() {
+    let flushes = RefCell::new(Vec::new());
+
+    {
+        let mut a = WithDropMut::new(23, |x| flushes.borrow_mut().push(*x));
+        let b = with_drop_mut(32, |x| flushes.borrow_mut().push(*x));
+
+        // Deref/DerefMut
+        *a += 42;
+        assert!(*a == 65);
+        assert!(*b == 32);
+
+        // Pre-drop
+        assert!(flushes.borrow().is_empty());
+    };
+
+    assert!(*flushes.borrow() == [32, 65]);
+}
+
+#[test]
+fn test_disarm_mut() {
+    let flushes = RefCell::new(Vec::new());
+
+    let a = WithDropMut::new(23, |x| flushes.borrow_mut().push(*x));
+    assert!(a.disarm() == 23);
+    assert!(flushes.borrow().is_empty());
+}
+
+#[test]
+fn test_defer() {
+    let ran = RefCell::new(false);
+
+    {
+        let _guard = defer(|| *ran.borrow_mut() = true);
+        assert!(!*ran.borrow());
+    };
+
+    assert!(*ran.borrow());
+}
+
+#[test]
+fn test_defer_disarm() {
+    let ran = RefCell::new(false);
+
+    let guard = Defer::new(|| *ran.borrow_mut() = true);
+    guard.disarm();
+
+    assert!(!*ran.borrow());
+}
+
+thread_local! {
+    static DROP_FN_LOG: RefCell<Vec<i32>> = const { RefCell::new(Vec::new()) };
+}
+
+fn push_drop(x: i32) {
+    DROP_FN_LOG.with(|log| log.borrow_mut().push(x));
+}
+
+#[test]
+fn test_with_drop_fn() {
+    {
+        let _a: WithDropFn<i32> = with_drop_fn(23, push_drop);
+        DROP_FN_LOG.with(|log| assert!(log.borrow().is_empty()));
+    };
+
+    DROP_FN_LOG.with(|log| assert!(*log.borrow() == [23]));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_with_drop_boxed() {
+    let drops = Rc::new(RefCell::new(Vec::new()));
+
+    {
+        let mut guards: Vec<WithDropDyn<i32>> = Vec::new();
+        let d = drops.clone();
+        guards.push(with_drop_boxed(23, move |x| d.borrow_mut().push(x)));
+        let d = drops.clone();
+        guards.push(with_drop_boxed(32, move |x| d.borrow_mut().push(x)));
+
+        assert!(*guards[0] == 23);
+        assert!(drops.borrow().is_empty());
+    };
+
+    assert!(*drops.borrow() == [23, 32]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_with_drop_boxed_disarm() {
+    let drops = Rc::new(RefCell::new(Vec::new()));
+    let d = drops.clone();
+
+    let a = with_drop_boxed(23, move |x| d.borrow_mut().push(x));
+    assert!(a.disarm() == 23);
+    assert!(drops.borrow().is_empty());
+}
+
+#[test]
+fn test_with_drop_ext() {
+    let drops = RefCell::new(Vec::new());
+
+    {
+        let _a = 23.with_drop(|x| drops.borrow_mut().push(x));
+        assert!(drops.borrow().is_empty());
+    };
+
+    assert!(*drops.borrow() == [23]);
+}